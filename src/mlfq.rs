@@ -0,0 +1,240 @@
+use core::isize;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use alloc::collections::VecDeque;
+use alloc::{sync::Arc, vec::Vec};
+use scheduler::BaseScheduler;
+
+// 多级反馈队列调度算法：任务从最高优先级（0级）开始运行，
+// 根据其在当前级别上累计消耗的运行时间自动下降优先级，
+// 并周期性地把所有任务提升回最高级，防止低优先级任务饿死。
+pub struct MlfqTask<T, const PRIO_LEVEL_NUM: usize> {
+    inner: T,
+    priority: AtomicUsize, // 在该struct内保证priority合法
+    // 当前优先级上累计运行的tick数，超过该级别的runtime_budget后会被降级。
+    accumulated_runtime: AtomicU64,
+}
+
+impl<T, const N: usize> MlfqTask<T, N> {
+    pub const fn new(inner: T) -> Self {
+        assert!(N > 0);
+        assert!(N <= (isize::MAX as usize) + 1);
+        Self {
+            inner,
+            priority: AtomicUsize::new(0),
+            accumulated_runtime: AtomicU64::new(0),
+        }
+    }
+
+    pub const fn inner(&self) -> &T {
+        &self.inner
+    }
+
+    fn set_priority(&self, prio: isize) -> bool {
+        if prio >= 0 && prio < N as isize {
+            self.priority.store(prio as usize, Ordering::Release);
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn get_priority(&self) -> usize {
+        self.priority.load(Ordering::Acquire)
+    }
+
+    fn reset_runtime(&self) {
+        self.accumulated_runtime.store(0, Ordering::Release);
+    }
+}
+
+pub struct MlfqScheduler<T, const PRIO_LEVEL_NUM: usize> {
+    ready_queues: Vec<VecDeque<Arc<MlfqTask<T, PRIO_LEVEL_NUM>>>>,
+    // bit p 为1，当且仅当ready_queues[p]非空。用于O(1)地找到最高优先级的非空队列。
+    // 同StatPrioScheduler：这里用Vec<u64>而不是定长数组[u64; (N+63)/64]，因为稳定版Rust
+    // 不支持用const泛型N派生数组长度（需要nightly的generic_const_exprs），是有意的取舍，
+    // 放弃了免分配的好处，换取和ready_queues一致的"init时按N分配一次"风格。
+    priority_bitmap: Vec<u64>,
+    // 每个优先级的运行时间预算（以tick为单位），级别越低预算越大。
+    runtime_budgets: Vec<u64>,
+    // 全局tick计数，达到reset_period后触发一次优先级重置，防止饥饿。
+    reset_period: usize,
+    ticks_since_reset: usize,
+}
+
+impl<T, const N: usize> MlfqScheduler<T, N> {
+    /// Creates a new empty [`MlfqScheduler`]. `reset_period` is the number of global
+    /// ticks after which every queued task is promoted back to the top level; pass
+    /// `0` to disable the periodic reset.
+    pub const fn new(reset_period: usize) -> Self {
+        assert!(N > 0);
+        assert!(N <= (isize::MAX as usize) + 1);
+        Self {
+            ready_queues: Vec::new(),
+            priority_bitmap: Vec::new(),
+            runtime_budgets: Vec::new(),
+            reset_period,
+            ticks_since_reset: 0,
+        }
+    }
+
+    /// get the name of scheduler
+    pub fn scheduler_name() -> &'static str {
+        "Multi-Level Feedback Queue"
+    }
+
+    /// Sets the runtime budget (in ticks) for the given priority level.
+    pub fn set_runtime_budget(&mut self, priority: usize, budget: u64) -> bool {
+        if priority < N {
+            self.runtime_budgets[priority] = budget;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    fn set_bitmap(&mut self, priority: usize) {
+        self.priority_bitmap[priority / 64] |= 1 << (priority % 64);
+    }
+
+    fn clear_bitmap(&mut self, priority: usize) {
+        self.priority_bitmap[priority / 64] &= !(1 << (priority % 64));
+    }
+
+    // 把所有排队中的任务放回最高优先级（0级），并清空它们的累计运行时间，用于周期性的饥饿保护。
+    fn reset_priorities(&mut self) {
+        for task in self.ready_queues[0].iter() {
+            task.reset_runtime();
+        }
+        for priority in 1 .. N {
+            while let Some(task) = self.ready_queues[priority].pop_front() {
+                task.set_priority(0);
+                task.reset_runtime();
+                self.ready_queues[0].push_back(task);
+            }
+            self.clear_bitmap(priority);
+        }
+        if !self.ready_queues[0].is_empty() {
+            self.set_bitmap(0);
+        }
+        self.ticks_since_reset = 0;
+    }
+}
+
+impl<T, const N: usize> BaseScheduler for MlfqScheduler<T, N> {
+    type SchedItem = Arc<MlfqTask<T, N>>;
+
+    fn init(&mut self) {
+        for _ in 0 .. N {
+            self.ready_queues.push(VecDeque::new());
+        }
+        for _ in 0 .. (N + 63) / 64 {
+            self.priority_bitmap.push(0);
+        }
+        // 级别越低（数值越大），运行时间预算越大，符合多级反馈队列的惯例。
+        for level in 0 .. N {
+            self.runtime_budgets.push((level as u64 + 1) * 10);
+        }
+    }
+
+    fn add_task(&mut self, task: Self::SchedItem) {
+        self.put_prev_task(task, false)
+    }
+
+    // 需要保证：每个任务只在调度器中存储了一个实例。即，调度器中不会有多个Arc指向同一任务。
+    fn remove_task(&mut self, task: &Self::SchedItem) -> Option<Self::SchedItem> {
+        for priority in 0 .. N {
+            for index in 0 .. self.ready_queues[priority].len() {
+                if Arc::ptr_eq(&self.ready_queues[priority][index], task) {
+                    let removed = self.ready_queues[priority].remove(index);
+                    if self.ready_queues[priority].is_empty() {
+                        self.clear_bitmap(priority);
+                    }
+                    return removed;
+                }
+            }
+        }
+        None
+    }
+
+    fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
+        let priority = self.highest_priority();
+        if priority == N {
+            return None;
+        }
+        let return_task = self.ready_queues[priority].pop_front();
+        if self.ready_queues[priority].is_empty() {
+            self.clear_bitmap(priority);
+        }
+        return_task
+    }
+
+    fn put_prev_task(&mut self, prev: Self::SchedItem, preempt: bool) {
+        let priority: usize = prev.get_priority();
+        if preempt {
+            self.ready_queues[priority].push_front(prev);
+        }
+        else {
+            self.ready_queues[priority].push_back(prev);
+        }
+        self.set_bitmap(priority);
+    }
+
+    fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        let current_prio = current.get_priority();
+        let self_prio = self.highest_priority();
+        let mut need_resched = self_prio < current_prio;
+
+        let runtime = current.accumulated_runtime.fetch_add(1, Ordering::AcqRel) + 1;
+        if current_prio + 1 < N && runtime > self.runtime_budgets[current_prio] {
+            current.set_priority(current_prio as isize + 1);
+            current.reset_runtime();
+            need_resched = true;
+        }
+
+        if self.reset_period > 0 {
+            self.ticks_since_reset += 1;
+            if self.ticks_since_reset >= self.reset_period {
+                self.reset_priorities();
+                need_resched = true;
+            }
+        }
+
+        need_resched
+    }
+
+    fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
+        let old_priority = task.get_priority();
+        if !task.set_priority(prio) {
+            return false;
+        }
+        let new_priority = prio as usize;
+
+        // 如果任务当前正在某个就绪队列中等待，需要把它从旧的队列搬到新优先级对应的队列，
+        // 否则调度器的判断依据（ready_queues/priority_bitmap）会与任务实际的优先级不一致。
+        for index in 0 .. self.ready_queues[old_priority].len() {
+            if Arc::ptr_eq(&self.ready_queues[old_priority][index], task) {
+                if let Some(moved) = self.ready_queues[old_priority].remove(index) {
+                    if self.ready_queues[old_priority].is_empty() {
+                        self.clear_bitmap(old_priority);
+                    }
+                    self.ready_queues[new_priority].push_back(moved);
+                    self.set_bitmap(new_priority);
+                }
+                break;
+            }
+        }
+        true
+    }
+
+    fn highest_priority(&self) -> usize {
+        for (word_index, word) in self.priority_bitmap.iter().enumerate() {
+            if *word != 0 {
+                return word_index * 64 + word.trailing_zeros() as usize;
+            }
+        }
+        N
+    }
+}
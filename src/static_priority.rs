@@ -1,24 +1,65 @@
 use core::isize;
-use core::sync::atomic::{AtomicUsize, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use alloc::collections::VecDeque;
 use alloc::{sync::Arc, vec::Vec};
 use scheduler::BaseScheduler;
 
+/// A task that can report its own scheduling intent to the scheduler.
+///
+/// This mirrors the `ScheduledItem`/`need_switch` design used by async-runtime
+/// and kernel schedulers: the task itself knows when it has blocked or wants to
+/// yield, and which group (e.g. address space) it belongs to, and the scheduler
+/// consults that instead of relying purely on priority.
+pub trait Schedulable {
+    /// Returns `true` if the task wants to be switched away from the CPU,
+    /// e.g. because it just blocked or voluntarily yielded.
+    fn need_switch(&self) -> bool;
+
+    /// Returns an identifier for the group (e.g. address space) this task
+    /// belongs to. Tasks sharing a group can be co-located to reduce
+    /// context-switch cost. Defaults to `0` (no grouping).
+    fn group(&self) -> usize {
+        0
+    }
+}
+
 // 静态优先级调度算法，相同优先级使用FIFO。
 // 调度算法不会自动调整任务的优先级，但可以手动调整。
 pub struct StatPrioTask<T, const PRIO_LEVEL_NUM: usize> {
     inner: T,
-    priority: AtomicUsize // 在该struct内保证priority合法
+    priority: AtomicUsize, // 在该struct内保证priority合法
+    // 当前时间片内剩余的tick数。0表示该任务所在优先级是SCHED_FIFO（无时间片限制）。
+    remaining_slice: AtomicUsize,
+    // 用户（通过set_priority）设定的基准优先级。任务因老化被临时提升后，
+    // 一旦被调度运行，就会被恢复到这个基准优先级。
+    base_priority: AtomicUsize,
+    // 任务在就绪队列中已经连续等待的tick数，用于老化（anti-starvation）判断。
+    wait_ticks: AtomicUsize,
+    // 任务是否希望被切换下CPU（例如刚刚阻塞或主动让出）。
+    need_switch: AtomicBool,
+    // 任务所属的地址空间/组号，用于减少不必要的地址空间切换。
+    group: usize,
 }
 
 impl<T, const N: usize> StatPrioTask<T, N> {
     pub const fn new(inner: T) -> Self {
+        Self::new_in_group(inner, 0)
+    }
+
+    /// Creates a new [`StatPrioTask`] belonging to the given scheduling `group`
+    /// (e.g. an address-space identifier), used by [`Schedulable::group`].
+    pub const fn new_in_group(inner: T, group: usize) -> Self {
         assert!(N > 0);
         assert!(N <= (isize::MAX as usize) + 1);
         Self {
             inner,
             priority: AtomicUsize::new(1),
+            remaining_slice: AtomicUsize::new(0),
+            base_priority: AtomicUsize::new(1),
+            wait_ticks: AtomicUsize::new(0),
+            need_switch: AtomicBool::new(false),
+            group,
         }
     }
 
@@ -26,9 +67,19 @@ impl<T, const N: usize> StatPrioTask<T, N> {
         &self.inner
     }
 
+    /// Marks this task as wanting to be switched away from the CPU, e.g. because
+    /// it just blocked on something or voluntarily yielded.
+    pub fn request_switch(&self) {
+        self.need_switch.store(true, Ordering::Release);
+    }
+
     fn set_priority(&self, prio: isize) -> bool {
         if prio >= 0 && prio < N as isize {
             self.priority.store(prio as usize, Ordering::Release);
+            self.base_priority.store(prio as usize, Ordering::Release);
+            // 调用方显式指定了优先级，丢弃之前累积的老化等待计数，
+            // 避免下一次task_tick立即把这次显式设置的优先级重新提升掉。
+            self.wait_ticks.store(0, Ordering::Release);
             true
         }
         else {
@@ -39,10 +90,48 @@ impl<T, const N: usize> StatPrioTask<T, N> {
     fn get_priority(&self) -> usize {
         self.priority.load(Ordering::Acquire)
     }
+
+    // 老化提升：只改变任务当前所在的队列位置，不改变用户设定的基准优先级。
+    fn set_aged_priority(&self, prio: usize) {
+        self.priority.store(prio, Ordering::Release);
+        self.wait_ticks.store(0, Ordering::Release);
+    }
+
+    // 任务被调度运行前，恢复到用户设定的基准优先级，并清零等待计数和切换请求。
+    fn restore_base_priority(&self) {
+        let base = self.base_priority.load(Ordering::Acquire);
+        self.priority.store(base, Ordering::Release);
+        self.wait_ticks.store(0, Ordering::Release);
+        self.need_switch.store(false, Ordering::Release);
+    }
+}
+
+impl<T, const N: usize> Schedulable for StatPrioTask<T, N> {
+    fn need_switch(&self) -> bool {
+        self.need_switch.load(Ordering::Acquire)
+    }
+
+    fn group(&self) -> usize {
+        self.group
+    }
 }
 
 pub struct StatPrioScheduler<T, const PRIO_LEVEL_NUM: usize> {
     ready_queues: Vec<VecDeque<Arc<StatPrioTask<T, PRIO_LEVEL_NUM>>>>,
+    // bit p 为1，当且仅当ready_queues[p]非空。用于O(1)地找到最高优先级的非空队列。
+    // 用Vec<u64>（长度(N+63)/64，在init中分配一次）而不是定长数组[u64; (N+63)/64]：
+    // 在稳定版Rust里，数组长度是从const泛型N派生的表达式，需要nightly的
+    // generic_const_exprs才能作为定长数组的长度使用；这里退而求其次，和
+    // ready_queues保持同样的"init时按N分配一次Vec"风格，放弃了免分配的好处。
+    priority_bitmap: Vec<u64>,
+    // 每个优先级对应的时间片长度（以tick为单位）。0表示该优先级是SCHED_FIFO，不受时间片限制。
+    time_slices: Vec<usize>,
+    // 老化阈值：任务在就绪队列中连续等待超过该tick数就会被提升aging_step级。0表示关闭老化（纯静态优先级）。
+    aging_threshold: usize,
+    // 每次老化提升的级数。
+    aging_step: usize,
+    // 当前被固定（pin）的地址空间/组号。pick_next_task会优先挑选同组任务，以减少地址空间切换。
+    pinned_group: Option<usize>,
 }
 
 impl<T, const N: usize> StatPrioScheduler<T, N> {
@@ -51,13 +140,104 @@ impl<T, const N: usize> StatPrioScheduler<T, N> {
         assert!(N > 0);
         assert!(N <= (isize::MAX as usize) + 1);
         Self {
-            ready_queues: Vec::new()
+            ready_queues: Vec::new(),
+            priority_bitmap: Vec::new(),
+            time_slices: Vec::new(),
+            aging_threshold: 0,
+            aging_step: 1,
+            pinned_group: None,
         }
     }
     /// get the name of scheduler
     pub fn scheduler_name() -> &'static str {
         "Static Priority"
     }
+
+    /// Sets the SCHED_RR time slice (in ticks) for the given priority level.
+    /// A slice of `0` makes the level behave as SCHED_FIFO (no time-slice preemption).
+    pub fn set_time_slice(&mut self, priority: usize, slice: usize) -> bool {
+        if priority < N {
+            self.time_slices[priority] = slice;
+            true
+        }
+        else {
+            false
+        }
+    }
+
+    /// Configures priority aging. A task that has waited in its ready queue for
+    /// more than `threshold` ticks is promoted `step` levels higher, and restored
+    /// to its base priority once it actually runs. Pass `threshold = 0` to disable
+    /// aging and keep pure static priority semantics.
+    pub fn set_aging(&mut self, threshold: usize, step: usize) {
+        self.aging_threshold = threshold;
+        self.aging_step = step.max(1);
+    }
+
+    /// Pins the scheduler to a scheduling group (e.g. the currently-loaded
+    /// address space). While pinned, [`BaseScheduler::pick_next_task`] prefers a
+    /// ready task from the same group over the front of the queue, to avoid an
+    /// unnecessary address-space switch.
+    ///
+    /// This breaks the FIFO-within-level ordering the rest of the scheduler
+    /// guarantees, so it only takes effect while aging ([`Self::set_aging`]) is
+    /// enabled: a same-priority task that keeps getting skipped for belonging to
+    /// the wrong group still ages and is promoted to a higher (strictly
+    /// preferred) level, where it keeps aging until it is served, at the latest
+    /// from level `0`, which ignores the group; this bounds how long it can be
+    /// skipped. With aging disabled the pin is ignored and plain FIFO order
+    /// is used, since nothing would otherwise stop indefinite starvation.
+    ///
+    /// Priority level `0` is always served strict FIFO regardless of the pin:
+    /// [`Self::age_waiting_tasks`] has nowhere higher to promote a level-0 task
+    /// to, so aging cannot bound starvation there.
+    pub fn pin_group(&mut self, group: usize) {
+        self.pinned_group = Some(group);
+    }
+
+    /// Clears any pinned scheduling group set by [`Self::pin_group`].
+    pub fn unpin_group(&mut self) {
+        self.pinned_group = None;
+    }
+
+    fn set_bitmap(&mut self, priority: usize) {
+        self.priority_bitmap[priority / 64] |= 1 << (priority % 64);
+    }
+
+    fn clear_bitmap(&mut self, priority: usize) {
+        self.priority_bitmap[priority / 64] &= !(1 << (priority % 64));
+    }
+
+    // 扫描每个非顶级队列中的所有任务并计时，超过老化阈值就把它搬到更高优先级的队列。
+    // 注意：必须给队列中的每一个任务计时，而不仅仅是队头——否则排在后面的任务要等到
+    // 它前面的任务先老化离开后才会开始计时，导致第k个任务的等待时间上界变成
+    // k * (aging_threshold + 1) 而不是aging_threshold + 1，破坏了老化本应提供的
+    // "有界等待"保证。这里的代价是O(该级别排队任务数)而不是O(N)。
+    fn age_waiting_tasks(&mut self) {
+        if self.aging_threshold == 0 {
+            return;
+        }
+        for priority in (1 .. N).rev() {
+            let mut index = 0;
+            while index < self.ready_queues[priority].len() {
+                let task = &self.ready_queues[priority][index];
+                let waited = task.wait_ticks.fetch_add(1, Ordering::AcqRel) + 1;
+                if waited > self.aging_threshold {
+                    let new_priority = priority.saturating_sub(self.aging_step);
+                    let task = self.ready_queues[priority].remove(index).unwrap();
+                    if self.ready_queues[priority].is_empty() {
+                        self.clear_bitmap(priority);
+                    }
+                    task.set_aged_priority(new_priority);
+                    self.ready_queues[new_priority].push_back(task);
+                    self.set_bitmap(new_priority);
+                }
+                else {
+                    index += 1;
+                }
+            }
+        }
+    }
 }
 
 impl<T, const N: usize> BaseScheduler for StatPrioScheduler<T, N> {
@@ -67,6 +247,12 @@ impl<T, const N: usize> BaseScheduler for StatPrioScheduler<T, N> {
         for _ in 0 .. N {
             self.ready_queues.push(VecDeque::new());
         }
+        for _ in 0 .. (N + 63) / 64 {
+            self.priority_bitmap.push(0);
+        }
+        for _ in 0 .. N {
+            self.time_slices.push(0);
+        }
     }
 
     fn add_task(&mut self, task: Self::SchedItem) {
@@ -78,7 +264,11 @@ impl<T, const N: usize> BaseScheduler for StatPrioScheduler<T, N> {
         for priority in 0 .. N {
             for index in 0 .. self.ready_queues[priority].len() {
                 if Arc::ptr_eq(&self.ready_queues[priority][index], task) {
-                    return self.ready_queues[priority].remove(index);
+                    let removed = self.ready_queues[priority].remove(index);
+                    if self.ready_queues[priority].is_empty() {
+                        self.clear_bitmap(priority);
+                    }
+                    return removed;
                 }
             }
         }
@@ -86,12 +276,34 @@ impl<T, const N: usize> BaseScheduler for StatPrioScheduler<T, N> {
     }
 
     fn pick_next_task(&mut self) -> Option<Self::SchedItem> {
-        let mut return_task: Option<Self::SchedItem> = None;
-        for priority in 0 .. N {
-            return_task = self.ready_queues[priority].pop_front();
-            if return_task.is_some() {
-                break;
-            }
+        let priority = self.highest_priority();
+        if priority == N {
+            return None;
+        }
+        let queue = &mut self.ready_queues[priority];
+        // 同地址空间快速路径：如果固定了某个组，优先在该优先级内挑选同组任务，
+        // 减少不必要的地址空间切换；找不到时退化为取队头。
+        // 这会打破同级队列内的FIFO顺序，因此只在老化开启、且不是0级（最高优先级）时
+        // 才生效——一个因为组不匹配而被反复跳过的任务仍然会老化，一旦被提升到更高
+        // 优先级，就不再受本级别的组选择影响，从而保证它不会被无限期饿死。0级没有
+        // 更高的级别可以提升，age_waiting_tasks也不会处理它，所以0级永远严格FIFO；
+        // 老化关闭时同样忽略pin，退化为纯FIFO。
+        let return_task = match self
+            .pinned_group
+            .filter(|_| self.aging_threshold > 0 && priority > 0)
+        {
+            Some(group) => match queue.iter().position(|task| task.group() == group) {
+                Some(index) => queue.remove(index),
+                None => queue.pop_front(),
+            },
+            None => queue.pop_front(),
+        };
+        if self.ready_queues[priority].is_empty() {
+            self.clear_bitmap(priority);
+        }
+        if let Some(task) = &return_task {
+            // 任务即将运行，如果它是被老化临时提升上来的，恢复到它的基准优先级。
+            task.restore_base_priority();
         }
         return_task
     }
@@ -102,24 +314,69 @@ impl<T, const N: usize> BaseScheduler for StatPrioScheduler<T, N> {
             self.ready_queues[priority].push_front(prev);
         }
         else {
+            // 未被抢占地放回队尾，说明该任务的时间片已经用尽（或本就没有时间片限制），
+            // 重置其时间片计数器，使其在下一轮被调度时重新获得完整的时间片。
+            prev.remaining_slice
+                .store(self.time_slices[priority], Ordering::Release);
             self.ready_queues[priority].push_back(prev);
         }
+        self.set_bitmap(priority);
     }
 
     fn task_tick(&mut self, current: &Self::SchedItem) -> bool {
+        self.age_waiting_tasks();
+
+        if current.need_switch() {
+            return true;
+        }
+
         let current_prio = current.get_priority();
         let self_prio = self.highest_priority();
-        self_prio > current_prio
+        if self_prio < current_prio {
+            return true;
+        }
+
+        let remaining = current.remaining_slice.load(Ordering::Acquire);
+        if remaining > 0 {
+            let remaining = remaining - 1;
+            current
+                .remaining_slice
+                .store(remaining, Ordering::Release);
+            if remaining == 0 {
+                return true;
+            }
+        }
+        false
     }
 
     fn set_priority(&mut self, task: &Self::SchedItem, prio: isize) -> bool {
-        task.set_priority(prio)
+        let old_priority = task.get_priority();
+        if !task.set_priority(prio) {
+            return false;
+        }
+        let new_priority = prio as usize;
+
+        // 如果任务当前正在某个就绪队列中等待，需要把它从旧的队列搬到新优先级对应的队列，
+        // 否则调度器的判断依据（ready_queues/priority_bitmap）会与任务实际的优先级不一致。
+        for index in 0 .. self.ready_queues[old_priority].len() {
+            if Arc::ptr_eq(&self.ready_queues[old_priority][index], task) {
+                if let Some(moved) = self.ready_queues[old_priority].remove(index) {
+                    if self.ready_queues[old_priority].is_empty() {
+                        self.clear_bitmap(old_priority);
+                    }
+                    self.ready_queues[new_priority].push_back(moved);
+                    self.set_bitmap(new_priority);
+                }
+                break;
+            }
+        }
+        true
     }
 
     fn highest_priority(&self) -> usize {
-        for priority in 0 .. N {
-            if !self.ready_queues[priority].is_empty() {
-                return priority;
+        for (word_index, word) in self.priority_bitmap.iter().enumerate() {
+            if *word != 0 {
+                return word_index * 64 + word.trailing_zeros() as usize;
             }
         }
         N